@@ -1,34 +1,153 @@
 //! This module implements the NIC structure, representing an e1000-compatible NIC.
 
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::mem::size_of;
 use kernel::device::bar::BAR;
 use kernel::device::manager::PhysicalDevice;
 use kernel::errno::Errno;
+use kernel::idt;
 use kernel::net::BindAddress;
 use kernel::net::MAC;
 use kernel::net;
+use kernel::util::lock::Mutex;
+
+/// I/O-mapped BAR offset: I/O Address register, used to select the register to access through
+/// `REG_IODATA`.
+const REG_IOADDR: u16 = 0x00;
+/// I/O-mapped BAR offset: I/O Data register, giving access to the register selected through
+/// `REG_IOADDR`.
+const REG_IODATA: u16 = 0x04;
 
 /// Register address: EEPROM/Flash Control & Data
 const REG_EECD: u16 = 0x10;
 /// Register address: EEPROM Read Register
 const REG_EERD: u16 = 0x14;
 
+/// The last EEPROM word covered by the checksum (0x00 to this address inclusive), holding the
+/// checksum itself.
+const EEPROM_CHECKSUM_WORD: u8 = 0x3f;
+/// The expected 16-bit sum of all EEPROM words, per the EEPROM checksum algorithm.
+const EEPROM_CHECKSUM_MAGIC: u16 = 0xbaba;
+
+/// Register address: Receive Address Low (register 0)
+const REG_RAL0: u16 = 0x5400;
+/// Register address: Receive Address High (register 0)
+const REG_RAH0: u16 = 0x5404;
+
+/// Register address (e1000e only): Transmit Arbitration Counter 0.
+const REG_TARC0: u16 = 0x3840;
+/// TARC0 flag: force legacy (non-extended) transmit descriptor mode. Required on the i217 /
+/// 82577LM for the card to accept the legacy `TXDesc` layout this driver writes.
+const TARC0_LEGACY_DESC: u32 = 1 << 24;
+
+/// PCI device ID: 82577LM (e1000e).
+const DEVICE_ID_82577LM: u16 = 0x10ea;
+/// PCI device ID: i217-LM (e1000e).
+const DEVICE_ID_I217_LM: u16 = 0x153a;
+/// PCI device ID: i217-V (e1000e).
+const DEVICE_ID_I217_V: u16 = 0x153b;
+
+/// Register address: Interrupt Cause Read
+const REG_ICR: u16 = 0x00C0;
+/// Register address: Interrupt Mask Set/Read
+const REG_IMS: u16 = 0x00D0;
+
+/// Interrupt cause: Link Status Change
+const INT_LSC: u32 = 1 << 2;
+/// Interrupt cause: Receive Descriptor Minimum Threshold Reached
+const INT_RXDMT0: u32 = 1 << 4;
+/// Interrupt cause: Receiver Timer Interrupt
+const INT_RXT0: u32 = 1 << 7;
+
+/// Register address: Receive Control
+const REG_RCTL: u16 = 0x0100;
+/// Register address: Receive Descriptor Base Address Low
+const REG_RDBAL: u16 = 0x2800;
+/// Register address: Receive Descriptor Base Address High
+const REG_RDBAH: u16 = 0x2804;
+/// Register address: Receive Descriptor Length
+const REG_RDLEN: u16 = 0x2808;
+/// Register address: Receive Descriptor Head
+const REG_RDH: u16 = 0x2810;
+/// Register address: Receive Descriptor Tail
+const REG_RDT: u16 = 0x2818;
+
+/// Register address: Transmit Control
+const REG_TCTL: u16 = 0x0400;
+/// Register address: Transmit IPG
+const REG_TIPG: u16 = 0x0410;
+/// Register address: Transmit Descriptor Base Address Low
+const REG_TDBAL: u16 = 0x3800;
+/// Register address: Transmit Descriptor Base Address High
+const REG_TDBAH: u16 = 0x3804;
+/// Register address: Transmit Descriptor Length
+const REG_TDLEN: u16 = 0x3808;
+/// Register address: Transmit Descriptor Head
+const REG_TDH: u16 = 0x3810;
+/// Register address: Transmit Descriptor Tail
+const REG_TDT: u16 = 0x3818;
+
+/// Receive Control flag: Receiver Enable
+const RCTL_EN: u32 = 1 << 1;
+/// Receive Control flag: Broadcast Accept Mode
+const RCTL_BAM: u32 = 1 << 15;
+/// Receive Control flag: Buffer Size 2048 bytes (with BSEX cleared)
+const RCTL_BSIZE_2048: u32 = 0 << 16;
+/// Receive Control flag: Strip Ethernet CRC
+const RCTL_SECRC: u32 = 1 << 26;
+
+/// Transmit Control flag: Transmitter Enable
+const TCTL_EN: u32 = 1 << 1;
+/// Transmit Control flag: Pad Short Packets
+const TCTL_PSP: u32 = 1 << 3;
+/// Transmit Control flag: Collision Threshold (bits 4-11), default 15
+const TCTL_CT: u32 = 15 << 4;
+/// Transmit Control flag: Collision Distance (bits 12-21), default 64
+const TCTL_COLD: u32 = 64 << 12;
+
+/// The number of receive descriptors in the ring.
+const RX_DESC_COUNT: usize = 32;
+/// The number of transmit descriptors in the ring.
+const TX_DESC_COUNT: usize = 8;
+
+/// The size in bytes of a single receive/transmit packet buffer.
+const DESC_BUFF_SIZE: usize = 2048;
+
 /// Transmit descriptor command flag: End of Packet
 const TX_CMD_EOP: u8 = 0x01;
 /// Transmit descriptor command flag: Insertion of FCS
 const TX_CMD_IFCS: u8 = 0x02;
-/// Transmit descriptor command flag: Insert checksum
-const TX_CMD_IC: u8 = 0x04;
 /// Transmit descriptor command flag: Report status
 const TX_CMD_RS: u8 = 0x08;
 /// Transmit descriptor command flag: Report Packet Sent
 const TX_CMD_RPS: u8 = 0x10;
 /// Transmit descriptor command flag: VLAN Packet Enable
 const TX_CMD_VLE: u8 = 0x40;
+/// Transmit descriptor command flag: Descriptor Extension (set on the data descriptor that
+/// follows a context descriptor, so the NIC knows to interpret the ring as extended descriptors
+/// rather than decoding the context descriptor's raw bytes as a legacy one)
+const TX_CMD_DEXT: u8 = 0x20;
 /// Transmit descriptor command flag: Interrupt Delay Enable
 const TX_CMD_IDE: u8 = 0x80;
 
+/// Transmit descriptor type (DTYP): Context descriptor.
+const TX_DTYP_CONTEXT: u32 = 0x0;
+
+/// TX context descriptor command flag (TUCMD): Packet is TCP (clear for UDP).
+const TUCMD_TCP: u8 = 0x01;
+/// TX context descriptor command flag (TUCMD): Insert IP checksum.
+const TUCMD_IP: u8 = 0x02;
+
+/// Receive descriptor status flag: Descriptor Done.
+const RX_STATUS_DD: u8 = 0x01;
+
 /// The receive descriptor.
 #[repr(packed)]
+#[derive(Clone, Copy)]
 struct RXDesc {
 	/// The physical address of the data.
 	addr: u64,
@@ -44,9 +163,23 @@ struct RXDesc {
 	special: u16,
 }
 
+impl Default for RXDesc {
+	fn default() -> Self {
+		Self {
+			addr: 0,
+			length: 0,
+			checksum: 0,
+			status: 0,
+			errors: 0,
+			special: 0,
+		}
+	}
+}
+
 // TODO: This is the legacy structure. Add support for the new version
 /// The transmit descriptor.
 #[repr(packed)]
+#[derive(Clone, Copy)]
 struct TXDesc {
 	/// The physical address of the data.
 	addr: u64,
@@ -64,56 +197,219 @@ struct TXDesc {
 	special: u16,
 }
 
-/// Structure representing a Network Interface Card.
-pub struct NIC {
-	/// TODO doc
-	status_reg: u16,
-	/// TODO doc
-	command_reg: u16,
+impl Default for TXDesc {
+	fn default() -> Self {
+		Self {
+			addr: 0,
+			length: 0,
+			cso: 0,
+			cmd: 0,
+			status: 0,
+			css: 0,
+			special: 0,
+		}
+	}
+}
 
-	/// The BAR0 of the device.
-	bar0: BAR,
+/// The transmit context descriptor, used ahead of one or more `TXDesc` to set up hardware
+/// checksum offload. Shares the same 16-byte layout as `TXDesc`.
+#[repr(packed)]
+#[derive(Clone, Copy)]
+struct TXContextDesc {
+	/// IP Checksum Start: the offset at which computation of the IP checksum starts.
+	ipcss: u8,
+	/// IP Checksum Offset: the offset at which the computed IP checksum is inserted.
+	ipcso: u8,
+	/// IP Checksum End: the offset of the last byte covered by the IP checksum.
+	ipcse: u16,
+	/// TU (TCP/UDP) Checksum Start: the offset at which computation of the TCP/UDP checksum
+	/// starts.
+	tucss: u8,
+	/// TU Checksum Offset: the offset at which the computed TCP/UDP checksum is inserted.
+	tucso: u8,
+	/// TU Checksum End: the offset of the last byte covered by the TCP/UDP checksum, or 0 for
+	/// the checksum to cover the data up to the end of the packet.
+	tucse: u16,
+	/// Payload Length (bits 0-19), Descriptor Type (bits 20-23) and TUCMD (bits 24-31).
+	paylen_dtyp_tucmd: u32,
+	/// Status flags.
+	status: u8,
+	/// Header length, used for TCP segmentation.
+	hdrlen: u8,
+	/// Maximum Segment Size, used for TCP segmentation.
+	mss: u16,
+}
 
-	/// Tells whether the EEPROM exist.
-	eeprom_exists: bool,
+impl Default for TXContextDesc {
+	fn default() -> Self {
+		Self {
+			ipcss: 0,
+			ipcso: 0,
+			ipcse: 0,
+			tucss: 0,
+			tucso: 0,
+			tucse: 0,
+			paylen_dtyp_tucmd: 0,
+			status: 0,
+			hdrlen: 0,
+			mss: 0,
+		}
+	}
+}
 
-	/// The NIC's mac address.
-	mac: [u8; 6],
+/// A transmit ring slot: either a legacy data descriptor or a context descriptor. Both forms
+/// share the same 16-byte hardware layout, so the ring stores this union rather than `TXDesc`
+/// directly.
+#[repr(C)]
+#[derive(Clone, Copy)]
+union TXSlot {
+	/// The data descriptor form.
+	data: TXDesc,
+	/// The context descriptor form.
+	context: TXContextDesc,
 }
 
-impl NIC {
-	/// Creates a new instance using the given device.
-	pub fn new(dev: &dyn PhysicalDevice) -> Result<Self, &str> {
-		let status_reg = dev.get_status_reg().ok_or("Invalid PCI informations for NIC!")?;
-		let command_reg = dev.get_command_reg().ok_or("Invalid PCI informations for NIC!")?;
+impl Default for TXSlot {
+	fn default() -> Self {
+		Self {
+			data: TXDesc::default(),
+		}
+	}
+}
 
-		let bar0 = dev.get_bars()[0].clone().ok_or("Invalid BAR for NIC!")?;
+/// A receive descriptor ring, over-aligned to the 16-byte boundary the NIC requires for the
+/// ring base address written to `RDBAL`/`RDBAH`. The descriptors themselves stay
+/// `#[repr(packed)]` (alignment 1) to keep their hardware layout accurate, so the alignment has
+/// to be carried by the wrapping array instead.
+#[repr(C, align(16))]
+struct RXRing([RXDesc; RX_DESC_COUNT]);
 
-		let mut n = Self {
-			status_reg,
-			command_reg,
+impl Default for RXRing {
+	fn default() -> Self {
+		Self([RXDesc::default(); RX_DESC_COUNT])
+	}
+}
 
-			bar0,
+impl core::ops::Deref for RXRing {
+	type Target = [RXDesc; RX_DESC_COUNT];
 
-			eeprom_exists: false,
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
 
-			mac: [0; 6],
-		};
-		n.detect_eeprom();
-		n.read_mac();
-		n.init_desc();
+impl core::ops::DerefMut for RXRing {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
 
-		Ok(n)
+/// A transmit descriptor ring, over-aligned to the 16-byte boundary the NIC requires for the
+/// ring base address written to `TDBAL`/`TDBAH`.
+#[repr(C, align(16))]
+struct TXRing([TXSlot; TX_DESC_COUNT]);
+
+impl Default for TXRing {
+	fn default() -> Self {
+		Self([TXSlot::default(); TX_DESC_COUNT])
+	}
+}
+
+impl core::ops::Deref for TXRing {
+	type Target = [TXSlot; TX_DESC_COUNT];
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl core::ops::DerefMut for TXRing {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
+
+/// The generation of e1000-compatible hardware driven by a `NIC` instance.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Generation {
+	/// The classic e1000.
+	E1000,
+	/// e1000e (82577LM / i217 and similar), which needs a few extra initialization steps.
+	E1000E,
+}
+
+/// Transport-layer protocol to checksum when requesting hardware checksum offload for an
+/// individual frame through `NIC::write_checksummed`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumProto {
+	/// The frame carries a TCP segment.
+	Tcp,
+	/// The frame carries a UDP datagram.
+	Udp,
+}
+
+/// The mutable state of a `NIC`: its hardware BAR and descriptor rings/queues.
+///
+/// This is shared between the normal call path and the interrupt handler registered for the
+/// device's IRQ line, so it is always accessed through a lock. In particular, the two-step
+/// I/O-port access done by `read_command` / `write_command` on I/O-mapped BARs is not atomic on
+/// its own and relies on that lock for mutual exclusion.
+struct NICState {
+	/// The BAR0 of the device.
+	bar0: BAR,
+	/// Tells whether BAR0 is an I/O-mapped BAR rather than a memory-mapped one.
+	io_mapped: bool,
+	/// The generation of hardware detected for this device.
+	generation: Generation,
+
+	/// Tells whether the EEPROM exists and is used. `NIC::new` clears it on e1000e parts and
+	/// when the EEPROM's checksum does not match, so that `read_mac` uses the Receive Address
+	/// registers instead.
+	eeprom_exists: bool,
+
+	/// The ring of receive descriptors.
+	rx_descs: Box<RXRing>,
+	/// The packet buffers associated with `rx_descs`.
+	rx_buffs: Box<[[u8; DESC_BUFF_SIZE]; RX_DESC_COUNT]>,
+	/// The index of the next receive descriptor to be read by software.
+	rx_cur: usize,
+	/// Queue of packets received by `handle_interrupt`, pending a call to `read`.
+	rx_queue: VecDeque<Vec<u8>>,
+
+	/// The ring of transmit descriptors.
+	tx_descs: Box<TXRing>,
+	/// The packet buffers associated with `tx_descs`.
+	tx_buffs: Box<[[u8; DESC_BUFF_SIZE]; TX_DESC_COUNT]>,
+	/// The index of the next transmit descriptor to be used by software.
+	tx_cur: usize,
+}
+
+impl NICState {
+	/// Returns the physical address of the given pointer.
+	///
+	/// The kernel is identity-mapped, so this is a direct cast.
+	fn phys_addr<T>(ptr: *const T) -> u64 {
+		ptr as u64
 	}
 
 	/// Sends a command to read at address `addr` in the NIC memory.
 	fn read_command(&self, addr: u16) -> u32 {
-		self.bar0.read::<u32>(addr as _) as _
+		if self.io_mapped {
+			self.bar0.write::<u32>(REG_IOADDR as _, addr as _);
+			self.bar0.read::<u32>(REG_IODATA as _) as _
+		} else {
+			self.bar0.read::<u32>(addr as _) as _
+		}
 	}
 
 	/// Sends a command to write the value `val` at address `addr` in the NIC memory.
 	fn write_command(&self, addr: u16, val: u32) {
-		self.bar0.write::<u32>(addr as _, val as _);
+		if self.io_mapped {
+			self.bar0.write::<u32>(REG_IOADDR as _, addr as _);
+			self.bar0.write::<u32>(REG_IODATA as _, val as _);
+		} else {
+			self.bar0.write::<u32>(addr as _, val as _);
+		}
 	}
 
 	/// Detects whether the EEPROM exists.
@@ -121,6 +417,17 @@ impl NIC {
 		self.eeprom_exists = self.read_command(REG_EECD) & (1 << 8) != 0;
 	}
 
+	/// Computes the 16-bit sum of all EEPROM words and checks it against
+	/// `EEPROM_CHECKSUM_MAGIC`.
+	///
+	/// Returns the computed sum (for diagnostics) and whether it matches, i.e. whether the
+	/// EEPROM's content can be trusted.
+	fn check_eeprom(&self) -> (u16, bool) {
+		let sum = (0..=EEPROM_CHECKSUM_WORD)
+			.fold(0u16, |sum, addr| sum.wrapping_add(self.eeprom_read(addr) as u16));
+		(sum, sum == EEPROM_CHECKSUM_MAGIC)
+	}
+
 	/// Reads from the EEPROM at address `addr`.
 	fn eeprom_read(&self, addr: u8) -> u32 {
 		// Acquire EEPROM
@@ -148,36 +455,301 @@ impl NIC {
 	}
 
 	/// Reads the MAC address from the NIC's EEPROM.
-	fn read_mac(&mut self) {
+	///
+	/// On cards without an EEPROM (e.g. the i217), the factory MAC is read directly from the
+	/// Receive Address registers instead.
+	fn read_mac(&self) -> [u8; 6] {
+		if !self.eeprom_exists {
+			let ral = self.read_command(REG_RAL0);
+			let rah = self.read_command(REG_RAH0);
+
+			return [
+				(ral & 0xff) as u8,
+				((ral >> 8) & 0xff) as u8,
+				((ral >> 16) & 0xff) as u8,
+				((ral >> 24) & 0xff) as u8,
+				(rah & 0xff) as u8,
+				((rah >> 8) & 0xff) as u8,
+			];
+		}
+
 		let val = self.eeprom_read(0);
-		self.mac[0] = (val & 0xff) as u8;
-		self.mac[1] = ((val >> 8) & 0xff) as u8;
+		let b0 = (val & 0xff) as u8;
+		let b1 = ((val >> 8) & 0xff) as u8;
 
 		let val = self.eeprom_read(1);
-		self.mac[2] = (val & 0xff) as u8;
-		self.mac[3] = ((val >> 8) & 0xff) as u8;
+		let b2 = (val & 0xff) as u8;
+		let b3 = ((val >> 8) & 0xff) as u8;
 
 		let val = self.eeprom_read(2);
-		self.mac[4] = (val & 0xff) as u8;
-		self.mac[5] = ((val >> 8) & 0xff) as u8;
+		let b4 = (val & 0xff) as u8;
+		let b5 = ((val >> 8) & 0xff) as u8;
+
+		[b0, b1, b2, b3, b4, b5]
 	}
 
 	/// Initializes transmit and receive descriptors.
-	fn init_desc(&self) {
-		// TODO
-		todo!();
+	fn init_desc(&mut self) {
+		// Bind each receive descriptor to its packet buffer
+		for i in 0..RX_DESC_COUNT {
+			self.rx_descs[i] = RXDesc {
+				addr: Self::phys_addr(self.rx_buffs[i].as_ptr()),
+				..Default::default()
+			};
+		}
+
+		let rx_ring_addr = Self::phys_addr(self.rx_descs.as_ptr());
+		self.write_command(REG_RDBAL, rx_ring_addr as u32);
+		self.write_command(REG_RDBAH, (rx_ring_addr >> 32) as u32);
+		self.write_command(REG_RDLEN, (RX_DESC_COUNT * size_of::<RXDesc>()) as u32);
+		self.write_command(REG_RDH, 0);
+		self.write_command(REG_RDT, (RX_DESC_COUNT - 1) as u32);
+		self.rx_cur = 0;
+
+		self.write_command(REG_RCTL, RCTL_EN | RCTL_BAM | RCTL_BSIZE_2048 | RCTL_SECRC);
+
+		// Bind each transmit descriptor to its packet buffer
+		for i in 0..TX_DESC_COUNT {
+			self.tx_descs[i] = TXSlot {
+				data: TXDesc {
+					addr: Self::phys_addr(self.tx_buffs[i].as_ptr()),
+					..Default::default()
+				},
+			};
+		}
+
+		let tx_ring_addr = Self::phys_addr(self.tx_descs.as_ptr());
+		self.write_command(REG_TDBAL, tx_ring_addr as u32);
+		self.write_command(REG_TDBAH, (tx_ring_addr >> 32) as u32);
+		self.write_command(REG_TDLEN, (TX_DESC_COUNT * size_of::<TXSlot>()) as u32);
+		self.write_command(REG_TDH, 0);
+
+		if self.generation == Generation::E1000E {
+			// The i217/82577LM must be told to accept legacy (non-extended) transmit
+			// descriptors before the tail register is touched.
+			self.write_command(REG_TARC0, self.read_command(REG_TARC0) | TARC0_LEGACY_DESC);
+		}
+		self.write_command(REG_TDT, 0);
+		self.tx_cur = 0;
+
+		// Inter Packet Gap: defaults recommended for IEEE 802.3 full-duplex
+		self.write_command(REG_TIPG, 10 | (8 << 10) | (6 << 20));
+		self.write_command(REG_TCTL, TCTL_EN | TCTL_PSP | TCTL_CT | TCTL_COLD);
 	}
 
-	/// Receives data using the given descriptor.
-	fn receive(&self, _rx_desc: &mut RXDesc) {
-		// TODO
-		todo!();
+	/// Enables the receive timer, receive descriptor minimum threshold, and link status change
+	/// interrupt causes. `handle_interrupt` acts on the two receive causes only; a link status
+	/// change is acknowledged (by the `ICR` read) but otherwise ignored.
+	fn enable_interrupts(&self) {
+		self.write_command(REG_IMS, INT_RXT0 | INT_RXDMT0 | INT_LSC);
 	}
 
-	/// Transmits the data of the given descriptor.
-	fn transmit(&self, _tx_desc: &mut TXDesc) {
-		// TODO
-		todo!();
+	/// Handles a pending interrupt from the NIC.
+	///
+	/// This must be called by the IRQ handler registered for this device's interrupt line.
+	fn handle_interrupt(&mut self) {
+		// Reading ICR also clears the pending causes
+		let cause = self.read_command(REG_ICR);
+
+		if cause & (INT_RXT0 | INT_RXDMT0) != 0 {
+			self.receive();
+		}
+	}
+
+	/// Walks the receive ring from the software tail, collecting every descriptor marked as
+	/// done (`RX_STATUS_DD`) into `rx_queue`, and hands the freed buffers back to the NIC.
+	fn receive(&mut self) {
+		loop {
+			let desc = &mut self.rx_descs[self.rx_cur];
+			if desc.status & RX_STATUS_DD == 0 {
+				break;
+			}
+
+			let length = desc.length as usize;
+			self.rx_queue.push_back(self.rx_buffs[self.rx_cur][..length].to_vec());
+
+			let desc = &mut self.rx_descs[self.rx_cur];
+			desc.status = 0;
+
+			self.write_command(REG_RDT, self.rx_cur as u32);
+			self.rx_cur = (self.rx_cur + 1) % RX_DESC_COUNT;
+		}
+	}
+
+	/// Transmits `buff` as a single packet, returning the number of bytes enqueued.
+	///
+	/// If `checksum` is set, a context descriptor requesting IP and TCP/UDP checksum insertion
+	/// is emitted ahead of the data descriptor, which is then marked with `TX_CMD_DEXT` so the
+	/// NIC interprets the pair as context+data rather than decoding the context descriptor's raw
+	/// bytes as a legacy one.
+	///
+	/// `checksum` is ignored (treated as `None`) on e1000e hardware (i217 / 82577LM): `init_desc`
+	/// sets `TARC0_LEGACY_DESC` on that generation, which forces legacy-only descriptor
+	/// interpretation on the whole TX ring and is incompatible with ever emitting a context
+	/// descriptor.
+	fn transmit(&mut self, buff: &[u8], checksum: Option<ChecksumProto>) -> u64 {
+		let checksum = checksum.filter(|_| self.generation != Generation::E1000E);
+
+		let mut cmd = TX_CMD_EOP | TX_CMD_IFCS | TX_CMD_RS;
+
+		if let Some(proto) = checksum {
+			// Offsets within a typical Ethernet+IPv4 frame: the 14-byte Ethernet header, then
+			// the 20-byte IP header (whose checksum sits at byte 10), then the TCP/UDP header
+			// (whose checksum sits 16 bytes in for TCP, 6 bytes in for UDP).
+			let (tucmd, tucso) = match proto {
+				ChecksumProto::Tcp => (TUCMD_IP | TUCMD_TCP, 14 + 20 + 16),
+				ChecksumProto::Udp => (TUCMD_IP, 14 + 20 + 6),
+			};
+			self.tx_descs[self.tx_cur] = TXSlot {
+				context: TXContextDesc {
+					ipcss: 14,
+					ipcso: 14 + 10,
+					ipcse: 14 + 20 - 1,
+					tucss: 14 + 20,
+					tucso,
+					tucse: 0,
+					paylen_dtyp_tucmd: (TX_DTYP_CONTEXT << 20) | ((tucmd as u32) << 24),
+					..Default::default()
+				},
+			};
+			self.tx_cur = (self.tx_cur + 1) % TX_DESC_COUNT;
+
+			cmd |= TX_CMD_DEXT;
+		}
+
+		let len = min(buff.len(), DESC_BUFF_SIZE);
+		self.tx_buffs[self.tx_cur][..len].copy_from_slice(&buff[..len]);
+
+		self.tx_descs[self.tx_cur] = TXSlot {
+			data: TXDesc {
+				addr: Self::phys_addr(self.tx_buffs[self.tx_cur].as_ptr()),
+				length: len as u16,
+				cso: 0,
+				cmd,
+				status: 0,
+				css: 0,
+				special: 0,
+			},
+		};
+
+		let next = (self.tx_cur + 1) % TX_DESC_COUNT;
+		self.write_command(REG_TDT, next as u32);
+		self.tx_cur = next;
+
+		len as u64
+	}
+}
+
+/// Structure representing a Network Interface Card.
+pub struct NIC {
+	/// TODO doc
+	status_reg: u16,
+	/// TODO doc
+	command_reg: u16,
+
+	/// The computed 16-bit sum of all EEPROM words, for diagnostics.
+	eeprom_checksum: u16,
+	/// Tells whether the EEPROM's checksum matches `EEPROM_CHECKSUM_MAGIC`. `None` if no EEPROM
+	/// is present on this device (e1000e parts have none), as opposed to `Some(false)` which
+	/// means an EEPROM is present but its checksum is corrupted.
+	eeprom_valid: Option<bool>,
+
+	/// The NIC's mac address.
+	mac: [u8; 6],
+
+	/// The mutable ring/register state, shared with the interrupt handler registered for this
+	/// device's IRQ line.
+	state: Arc<Mutex<NICState>>,
+}
+
+impl NIC {
+	/// Creates a new instance using the given device.
+	///
+	/// The NIC's receive/transmit ring state is shared with the interrupt handler registered for
+	/// the device's IRQ line through an internal lock, so every access to it, including the
+	/// two-step I/O-port register access `NICState::read_command` / `write_command` perform on
+	/// I/O-mapped BARs, stays consistent with whatever `handle_interrupt` is doing concurrently.
+	pub fn new(dev: &dyn PhysicalDevice) -> Result<Self, &str> {
+		let status_reg = dev.get_status_reg().ok_or("Invalid PCI informations for NIC!")?;
+		let command_reg = dev.get_command_reg().ok_or("Invalid PCI informations for NIC!")?;
+		let irq = dev.get_interrupt_line().ok_or("Invalid interrupt line for NIC!")?;
+
+		let bar0 = dev.get_bars()[0].clone().ok_or("Invalid BAR for NIC!")?;
+		let io_mapped = matches!(bar0, BAR::IOSpace { .. });
+
+		let generation = match dev.get_device_id() {
+			Some(DEVICE_ID_82577LM) | Some(DEVICE_ID_I217_LM) | Some(DEVICE_ID_I217_V) => {
+				Generation::E1000E
+			}
+			_ => Generation::E1000,
+		};
+
+		let mut state = NICState {
+			bar0,
+			io_mapped,
+			generation,
+
+			eeprom_exists: false,
+
+			rx_descs: Box::new(RXRing::default()),
+			rx_buffs: Box::new([[0; DESC_BUFF_SIZE]; RX_DESC_COUNT]),
+			rx_cur: 0,
+			rx_queue: VecDeque::new(),
+
+			tx_descs: Box::new(TXRing::default()),
+			tx_buffs: Box::new([[0; DESC_BUFF_SIZE]; TX_DESC_COUNT]),
+			tx_cur: 0,
+		};
+		state.detect_eeprom();
+
+		let (eeprom_checksum, eeprom_valid) = if generation == Generation::E1000E {
+			// e1000e parts (82577LM / i217) have no classic EEPROM; read the MAC from the
+			// memory-mapped Receive Address registers instead.
+			state.eeprom_exists = false;
+			(0, None)
+		} else if state.eeprom_exists {
+			let (sum, valid) = state.check_eeprom();
+			if !valid {
+				// The EEPROM's content cannot be trusted; fall back to the Receive Address
+				// registers for the MAC instead of a possibly-garbage address.
+				state.eeprom_exists = false;
+			}
+			(sum, Some(valid))
+		} else {
+			(0, None)
+		};
+		let mac = state.read_mac();
+		state.init_desc();
+
+		let state = Arc::new(Mutex::new(state));
+		let callback_state = state.clone();
+		idt::register_callback(irq, move || callback_state.lock().handle_interrupt())
+			.map_err(|_| "Failed to register the NIC's interrupt handler!")?;
+		// Only unmask interrupts once the callback above is registered, so the device is never
+		// left holding an enabled, unhandled interrupt line.
+		state.lock().enable_interrupts();
+
+		Ok(Self {
+			status_reg,
+			command_reg,
+
+			eeprom_checksum,
+			eeprom_valid,
+
+			mac,
+
+			state,
+		})
+	}
+
+	/// Transmits `buff` as a single packet with hardware checksum offload requested for `proto`,
+	/// returning the number of bytes enqueued.
+	///
+	/// This is ignored on e1000e hardware (i217 / 82577LM), which cannot mix context descriptors
+	/// with the legacy descriptor format `NICState::init_desc` configures for it; the frame is
+	/// still sent, just without offload.
+	pub fn write_checksummed(&mut self, buff: &[u8], proto: ChecksumProto) -> Result<u64, Errno> {
+		Ok(self.state.lock().transmit(buff, Some(proto)))
 	}
 }
 
@@ -201,29 +773,20 @@ impl net::Interface for NIC {
 		todo!();
 	}
 
-	fn read(&mut self, _buff: &mut [u8]) -> Result<(u64, bool), Errno> {
-		// TODO
-		todo!();
-	}
-
-	fn write(&mut self, _buff: &[u8]) -> Result<u64, Errno> {
-		// TODO do asynchronously
-		/*let mut i = 0;
-
-		while i < buff.len() {
-			let desc = &mut self.tx_descs[self.curr_tx_desc];
-			desc.addr = buff.as_ptr() as _;
-			desc.length = min(buff.len() - i, u16::MAX as usize) as _;
-			desc.cmd = ; // TODO
-			desc.status = 0;
+	fn read(&mut self, buff: &mut [u8]) -> Result<(u64, bool), Errno> {
+		let mut state = self.state.lock();
+		let Some(packet) = state.rx_queue.pop_front() else {
+			return Err(Errno::EAGAIN);
+		};
 
-			let next_desc = (self.curr_tx_desc + 1) % TX_DESC_COUNT;
-			self.write_command(, next_desc);
+		let len = min(buff.len(), packet.len());
+		buff[..len].copy_from_slice(&packet[..len]);
 
-			// TODO wait until status is not zero
-		}
+		Ok((len as u64, !state.rx_queue.is_empty()))
+	}
 
-		Ok(i as _)*/
-		todo!();
+	fn write(&mut self, buff: &[u8]) -> Result<u64, Errno> {
+		// TODO do asynchronously, waiting for the descriptor's status to be non-zero
+		Ok(self.state.lock().transmit(buff, None))
 	}
 }